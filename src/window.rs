@@ -1,8 +1,10 @@
-use std::io::{stdin, stdout, Stdout, Write};
-use std::sync::{Arc, Mutex};
+use std::io::{stdout, Stdout, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crossterm::event::{Event as CEvent, EventStream, KeyEvent};
+use futures::StreamExt;
 
-use termion::event::{Event, Key};
-use termion::input::TermRead;
 use termion::screen::AlternateScreen;
 
 use crate::term;
@@ -11,6 +13,36 @@ use crate::term::ScreenExt;
 use crate::coordinates::{Coordinates, Position, Size};
 use crate::widget::Widget;
 
+mod history;
+use self::history::{History, HistoryCursor};
+
+mod watcher;
+use self::watcher::FsWatcher;
+
+mod pty;
+use self::pty::PtyPane;
+
+mod preview;
+pub use self::preview::Preview;
+
+mod completion;
+pub use self::completion::{
+    find_bins, longest_common_prefix, BinaryCompleter, Candidate, Completer, FuzzyCompleter,
+    PathCompleter,
+};
+
+/// Messages that can wake up a running `Window` without it having to block on
+/// terminal input. All of them arrive on `event_tx` from widgets and
+/// background tasks; keys read from the crossterm event stream are handled
+/// by `Window::run` directly and are not wrapped in `Input`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Input(KeyEvent),
+    Redraw,
+    Status(String),
+    Fs(PathBuf),
+}
+
 pub struct Window<T>
 where
     T: Widget,
@@ -20,6 +52,10 @@ where
     pub status: Arc<Mutex<Option<String>>>,
     pub screen: AlternateScreen<Box<Stdout>>,
     pub coordinates: Coordinates,
+    pub event_tx: async_channel::Sender<Event>,
+    event_rx: async_channel::Receiver<Event>,
+    fs_watcher: Option<FsWatcher>,
+    pty: Option<PtyPane>,
 }
 
 impl<T> Window<T>
@@ -30,6 +66,9 @@ where
         let mut screen = AlternateScreen::from(Box::new(stdout()));
         screen.cursor_hide();
         let (xsize, ysize) = termion::terminal_size().unwrap();
+        let (event_tx, event_rx) = async_channel::unbounded();
+        EVENT_TX.set(event_tx.clone()).ok();
+
         let mut win = Window::<T> {
             selection: 0,
             widget: widget,
@@ -39,6 +78,10 @@ where
                 size: Size((xsize, ysize)),
                 position: Position((1, 1)),
             },
+            event_tx,
+            event_rx,
+            fs_watcher: None,
+            pty: None,
         };
 
         win.widget.set_coordinates(&Coordinates {
@@ -49,32 +92,122 @@ where
         win
     }
 
+    /// Start watching `path` for changes, redrawing via `Event::Fs` whenever
+    /// something underneath it is created, removed or renamed. Lazily
+    /// starts the background watcher task on first use.
+    pub fn watch(&mut self, path: &std::path::Path) {
+        if self.fs_watcher.is_none() {
+            match FsWatcher::new(self.event_tx.clone()) {
+                Ok(watcher) => self.fs_watcher = Some(watcher),
+                Err(err) => {
+                    show_status(&format!("live-reload disabled: {}", err));
+                    return;
+                }
+            }
+        }
+
+        self.fs_watcher.as_mut().unwrap().watch(path);
+    }
+
+    pub fn unwatch(&mut self, path: &std::path::Path) {
+        if let Some(watcher) = self.fs_watcher.as_mut() {
+            watcher.unwatch(path);
+        }
+    }
+
+    /// Run `cmd` inside a pseudo-terminal pane that takes over keyboard
+    /// focus and is drawn on top of the widget until the pane is closed.
+    /// Once the child has exited, the next key press closes the pane.
+    pub fn spawn_pty(&mut self, cmd: &str, args: &[String]) -> std::io::Result<()> {
+        self.pty = Some(PtyPane::spawn(
+            cmd,
+            args,
+            self.widget.get_coordinates().clone(),
+            self.event_tx.clone(),
+        )?);
+        Ok(())
+    }
+
+    pub fn close_pty(&mut self) {
+        self.pty = None;
+    }
+
+    /// Grow the focused PTY pane to cover the whole alternate screen, or
+    /// shrink it back to its widget sub-region if it's already fullscreen.
+    pub fn toggle_pty_fullscreen(&mut self) {
+        if let Some(pty) = self.pty.as_mut() {
+            pty.toggle_fullscreen(self.coordinates.clone());
+        }
+    }
+
     pub fn draw(&mut self) {
-        let output = self.widget.get_drawlist() + &self.widget.get_header_drawlist()
+        let mut output = self.widget.get_drawlist() + &self.widget.get_header_drawlist()
             + &self.widget.get_footer_drawlist();
+
+        if let Some(pty) = self.pty.as_ref() {
+            output += &pty.get_drawlist();
+        }
+
         self.screen.write(output.as_ref()).unwrap();
 
         self.screen.flush().unwrap();
-    }
 
-    // pub fn show_status(status: &str) {
-    //     show_status(status);
-    // }
-
-    // pub fn draw_status() {
-    //     draw_status();
-    // }
+        draw_status();
+    }
 
-    // pub fn clear_status() {
-    //     Self::show_status("");
-    // }
+    /// Drive the window until the crossterm input stream closes. Key events
+    /// go to the PTY pane while one is open (F11 toggles its fullscreen
+    /// mode) and to the widget otherwise. Everything posted on `event_tx`
+    /// ends in a redraw so idle widgets can still update the screen between
+    /// keystrokes: `Status` sets the status line first, `Fs` calls the
+    /// widget's `on_fs_event` and `refresh`, and `Input` goes to the widget.
+    pub async fn run(&mut self) {
+        let mut reader = EventStream::new();
 
-    pub fn handle_input(&mut self) {
-        for event in stdin().events() {
-            //Self::clear_status();
-            let event = event.unwrap();
-            self.widget.on_event(event);
-            self.draw();
+        loop {
+            tokio::select! {
+                maybe_event = reader.next() => {
+                    match maybe_event {
+                        Some(Ok(CEvent::Key(key))) => {
+                            if key.code == crossterm::event::KeyCode::F(11) {
+                                self.toggle_pty_fullscreen();
+                            } else if let Some(pty) = self.pty.as_mut() {
+                                if pty.exit_status().is_some() {
+                                    self.pty = None;
+                                } else {
+                                    pty.send_key(key);
+                                }
+                            } else {
+                                self.widget.on_event(key);
+                            }
+                            self.draw();
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(_)) | None => break,
+                    }
+                }
+                event = self.event_rx.recv() => {
+                    match event {
+                        Ok(Event::Status(status)) => {
+                            *self.status.lock().unwrap() = Some(status);
+                            self.draw();
+                        }
+                        Ok(Event::Redraw) => {
+                            self.draw();
+                        }
+                        Ok(Event::Fs(path)) => {
+                            self.widget.on_fs_event(&path);
+                            self.widget.refresh();
+                            self.draw();
+                        }
+                        Ok(Event::Input(key)) => {
+                            self.widget.on_event(key);
+                            self.draw();
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
         }
     }
 }
@@ -105,9 +238,14 @@ lazy_static! {
     static ref STATUS_BAR_CONTENT: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
 }
 
+/// Sender half of the event bus of whichever `Window` is currently running.
+/// Set once in `Window::new`, so free functions like `show_status` can post
+/// without needing a handle to the window itself.
+static EVENT_TX: OnceLock<async_channel::Sender<Event>> = OnceLock::new();
+
 pub fn draw_status() {
     let xsize = term::xsize() as u16;
-    let status = STATUS_BAR_CONTENT.try_lock().unwrap().clone();
+    let status = STATUS_BAR_CONTENT.lock().unwrap().clone();
 
     status.or(Some("".to_string())).and_then(|status| {
         write!(
@@ -127,14 +265,30 @@ pub fn draw_status() {
 
 pub fn show_status(status: &str) {
     {
-        let mut status_content = STATUS_BAR_CONTENT.try_lock().unwrap();
+        let mut status_content = STATUS_BAR_CONTENT.lock().unwrap();
         *status_content = Some(status.to_string());
     }
-    draw_status();
+
+    // Posting the event (rather than drawing here directly) lets the
+    // running `Window` pick up the redraw through its own event loop
+    // instead of every caller racing to write the status line straight to
+    // stdout.
+    if let Some(tx) = EVENT_TX.get() {
+        tx.try_send(Event::Status(status.to_string())).ok();
+    }
 }
 
-pub fn minibuffer(query: &str) -> Option<String> {
+/// Runs its own blocking `stdin().events()` loop rather than going through
+/// `Window::run`'s event bus, so it calls `draw_status()` directly after
+/// each `show_status()` instead of relying on the (undrained, while this
+/// function blocks) `event_rx` to trigger the redraw.
+pub fn minibuffer(query: &str, completer: &dyn Completer) -> Option<String> {
+    use std::io::stdin;
+    use termion::event::{Event as TEvent, Key};
+    use termion::input::TermRead;
+
     show_status(&(query.to_string() + ": "));
+    draw_status();
     write!(stdout(), "{}{}",
            termion::cursor::Show,
            termion::cursor::Save).unwrap();
@@ -143,34 +297,52 @@ pub fn minibuffer(query: &str) -> Option<String> {
     let mut buffer = "".to_string();
     let mut pos = 0;
 
+    let history = History::load();
+    let mut history_cursor = HistoryCursor::new(&history);
+
+    // Candidates from the most recent Tab press, the byte range of `buffer`
+    // they replace, and which one is currently selected, so a repeated Tab
+    // cycles instead of recomputing completions from scratch.
+    let mut tab_state: Option<(Vec<Candidate>, usize, Option<usize>)> = None;
+
     for key in stdin().events() {
 
+        let is_tab_key = matches!(key, Ok(TEvent::Key(Key::Char('\t'))) | Ok(TEvent::Key(Key::BackTab)));
+        if !is_tab_key {
+            tab_state = None;
+        }
+
         match key {
-            Ok(Event::Key(key)) => match key {
+            Ok(TEvent::Key(key)) => match key {
                 Key::Esc | Key::Ctrl('c') => break,
                 Key::Char('\n') => {
                     if buffer == "" {
                         return None;
                     } else {
+                        drop(history_cursor);
+                        let mut history = history;
+                        history.submit(&buffer);
                         return Some(buffer);
                     }
                 }
-                Key::Char('\t') => {
-                    if !buffer.ends_with(" ") {
-                        let part = buffer.rsplitn(2, " ").take(1)
-                            .map(|s| s.to_string()).collect::<String>();
-                        let completions = find_bins(&part);
-
-                        if !completions.is_empty() {
-                            buffer = buffer[..buffer.len() - part.len()].to_string();
-                            buffer.push_str(&completions[0]);
-                            pos += &completions[0].len() - part.len();
-                        }
-                    } else {
-                        buffer += "$s";
-                        pos += 2
+                Key::Up | Key::Ctrl('p') => {
+                    if let Some(recalled) = history_cursor.prev(&buffer) {
+                        buffer = recalled;
+                        pos = buffer.len();
+                    }
+                }
+                Key::Down | Key::Ctrl('n') => {
+                    if let Some(recalled) = history_cursor.next() {
+                        buffer = recalled;
+                        pos = buffer.len();
                     }
                 }
+                Key::Char('\t') => {
+                    cycle_completion(&mut buffer, &mut pos, &mut tab_state, completer, 1);
+                }
+                Key::BackTab => {
+                    cycle_completion(&mut buffer, &mut pos, &mut tab_state, completer, -1);
+                }
                 Key::Backspace => {
                     if pos != 0 {
                         buffer.remove(pos - 1);
@@ -195,6 +367,7 @@ pub fn minibuffer(query: &str) -> Option<String> {
                 Key::Ctrl('a') => { pos = 0 },
                 Key::Ctrl('e') => { pos = buffer.len(); },
                 Key::Char(key) => {
+                    history_cursor.reset();
                     buffer.insert(pos, key);
                     pos += 1;
                 }
@@ -202,7 +375,10 @@ pub fn minibuffer(query: &str) -> Option<String> {
             },
             _ => {}
         }
-        show_status(&(query.to_string() + ": " + &buffer));
+        if !is_tab_key {
+            show_status(&(query.to_string() + ": " + &buffer));
+            draw_status();
+        }
 
         write!(stdout(), "{}", termion::cursor::Restore).unwrap();
         stdout().flush().unwrap();
@@ -216,22 +392,51 @@ pub fn minibuffer(query: &str) -> Option<String> {
     None
 }
 
-pub fn find_bins(comp_name: &str) -> Vec<String> {
-    let paths = std::env::var_os("PATH").unwrap()
-        .to_string_lossy()
-        .split(":")
-        .map(|s| s.to_string())
-        .collect::<Vec<String>>();
-
-    paths.iter().map(|path| {
-        std::fs::read_dir(path).unwrap().flat_map(|file| {
-            let file = file.unwrap();
-            let name = file.file_name().into_string().unwrap();
-            if name.starts_with(comp_name) {
-                Some(name)
-            } else {
-                None
-            }
-        }).collect::<Vec<String>>()
-    }).flatten().collect::<Vec<String>>()
+/// Drives Tab/Shift-Tab in `minibuffer`: the first press extends `buffer` to
+/// the longest common prefix of all candidates for the current word, and
+/// each subsequent press (in `direction`) cycles through them, showing the
+/// selection in the status bar.
+fn cycle_completion(
+    buffer: &mut String,
+    pos: &mut usize,
+    tab_state: &mut Option<(Vec<Candidate>, usize, Option<usize>)>,
+    completer: &dyn Completer,
+    direction: i32,
+) {
+    if tab_state.is_none() {
+        let part = buffer.rsplitn(2, " ").take(1).map(|s| s.to_string()).collect::<String>();
+        let word_start = buffer.len() - part.len();
+        let candidates = completer.complete(&part, buffer);
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let prefix = longest_common_prefix(&candidates);
+        if prefix.len() > part.len() {
+            buffer.replace_range(word_start.., &prefix);
+            *pos = buffer.len();
+        }
+        show_status(buffer);
+        draw_status();
+
+        // No candidate selected yet — the next Tab starts cycling at 0
+        // rather than skipping straight to candidate 1.
+        *tab_state = Some((candidates, word_start, None));
+        return;
+    }
+
+    let (candidates, word_start, index) = tab_state.as_mut().unwrap();
+    let len = candidates.len() as i32;
+    let next_index = match *index {
+        None => 0,
+        Some(i) => (((i as i32 + direction) % len + len) % len) as usize,
+    };
+    *index = Some(next_index);
+
+    let candidate = &candidates[next_index];
+    buffer.replace_range(*word_start.., &candidate.text);
+    *pos = buffer.len();
+    show_status(&format!("[{}/{}] {}", next_index + 1, candidates.len(), candidate.display));
+    draw_status();
 }
@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+
+/// One completion option. `text` is what gets spliced into the buffer;
+/// `display` is what's shown while cycling through candidates in the status
+/// bar (usually the same, but path completions show the bare file name
+/// while completing to the full path).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub text: String,
+    pub display: String,
+}
+
+impl Candidate {
+    fn new(text: impl Into<String>) -> Candidate {
+        let text = text.into();
+        Candidate { display: text.clone(), text }
+    }
+}
+
+/// Completes the word under the cursor in a `minibuffer` prompt. Different
+/// call sites (running a command vs. entering a path) pass a different
+/// `Completer` so Tab does the context-appropriate thing.
+pub trait Completer {
+    fn complete(&self, word: &str, full_line: &str) -> Vec<Candidate>;
+}
+
+/// Completes executable names found on `$PATH`, same as the original
+/// `find_bins`-only behavior.
+pub struct BinaryCompleter;
+
+impl Completer for BinaryCompleter {
+    fn complete(&self, word: &str, _full_line: &str) -> Vec<Candidate> {
+        find_bins(word).into_iter().map(Candidate::new).collect()
+    }
+}
+
+/// Completes filesystem paths, expanding a leading `~` and completing
+/// against the directory contents of the word's dirname.
+pub struct PathCompleter;
+
+impl Completer for PathCompleter {
+    fn complete(&self, word: &str, _full_line: &str) -> Vec<Candidate> {
+        let expanded = expand_tilde(word);
+        let (dir, prefix) = split_dir_prefix(&expanded);
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(&prefix) {
+                    return None;
+                }
+                let mut full = dir.clone();
+                full.push(&name);
+                Some(Candidate {
+                    text: full.to_string_lossy().into_owned(),
+                    display: name,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Scores `candidates` by subsequence match against the typed word, with
+/// bonuses for contiguous runs and word-boundary starts, the way a fuzzy
+/// file finder would.
+pub struct FuzzyCompleter<'a> {
+    pub candidates: &'a [String],
+}
+
+impl<'a> Completer for FuzzyCompleter<'a> {
+    fn complete(&self, word: &str, _full_line: &str) -> Vec<Candidate> {
+        let mut scored: Vec<(i64, &String)> = self
+            .candidates
+            .iter()
+            .filter_map(|candidate| fuzzy_score(word, candidate).map(|score| (score, candidate)))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.into_iter().map(|(_, c)| Candidate::new(c.clone())).collect()
+    }
+}
+
+fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+
+    let mut score = 0i64;
+    let mut pi = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (ci, &ch) in candidate.iter().enumerate() {
+        if pi >= pattern.len() {
+            break;
+        }
+        if ch.to_ascii_lowercase() != pattern[pi].to_ascii_lowercase() {
+            continue;
+        }
+
+        score += 1;
+        if last_match == Some(ci.wrapping_sub(1)) {
+            score += 5;
+        }
+        if ci == 0 || matches!(candidate[ci - 1], '/' | '_' | '-' | '.') {
+            score += 10;
+        }
+
+        last_match = Some(ci);
+        pi += 1;
+    }
+
+    if pi == pattern.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+/// The longest prefix shared by every candidate's `text`, used to extend the
+/// buffer on the first Tab press before cycling kicks in.
+pub fn longest_common_prefix(candidates: &[Candidate]) -> String {
+    let mut iter = candidates.iter();
+    let first = match iter.next() {
+        Some(candidate) => &candidate.text,
+        None => return String::new(),
+    };
+
+    let mut prefix_len = first.chars().count();
+    for candidate in iter {
+        let shared = first
+            .chars()
+            .zip(candidate.text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        prefix_len = prefix_len.min(shared);
+    }
+
+    first.chars().take(prefix_len).collect()
+}
+
+fn expand_tilde(word: &str) -> String {
+    if let Some(rest) = word.strip_prefix('~') {
+        if let Some(home) = std::env::var_os("HOME") {
+            return format!("{}{}", home.to_string_lossy(), rest);
+        }
+    }
+    word.to_string()
+}
+
+/// Splits `word` into the directory to list and the filename prefix to
+/// match within it, e.g. `src/win` -> (`src`, `win`).
+fn split_dir_prefix(word: &str) -> (PathBuf, String) {
+    match word.rfind('/') {
+        Some(i) => (PathBuf::from(&word[..=i]), word[i + 1..].to_string()),
+        None => (PathBuf::from("."), word.to_string()),
+    }
+}
+
+/// Completes executable names found on `$PATH`. Unreadable directories
+/// (missing, permission denied, a stale symlink) are skipped rather than
+/// panicking, since a single broken `$PATH` entry shouldn't break
+/// completion entirely.
+pub fn find_bins(comp_name: &str) -> Vec<String> {
+    let paths = match std::env::var_os("PATH") {
+        Some(paths) => paths,
+        None => return Vec::new(),
+    };
+
+    std::env::split_paths(&paths)
+        .filter_map(|path: PathBuf| std::fs::read_dir(&path).ok())
+        .flat_map(|entries| {
+            entries.flatten().filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if name.starts_with(comp_name) {
+                    Some(name)
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
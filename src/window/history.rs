@@ -0,0 +1,132 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Persistent history of lines submitted through `minibuffer`, one entry per
+/// line in an XDG data-dir file (`$XDG_DATA_HOME/hunter/history` or
+/// `~/.local/share/hunter/history`).
+pub struct History {
+    entries: Vec<String>,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load() -> History {
+        let path = history_path();
+        let entries = fs::read_to_string(&path)
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_string())
+                    .collect()
+            })
+            .unwrap_or_else(|_| Vec::new());
+
+        History { entries, path }
+    }
+
+    /// Record a submitted line, skipping empty input and consecutive
+    /// duplicates, and append it to the on-disk history file.
+    pub fn submit(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        if self.entries.last().map(|last| last == line).unwrap_or(false) {
+            return;
+        }
+
+        self.entries.push(line.to_string());
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&self.path) {
+            writeln!(file, "{}", line).ok();
+        }
+    }
+}
+
+fn history_path() -> PathBuf {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    data_dir.join("hunter").join("history")
+}
+
+/// Walks `History` backward/forward while a `minibuffer` prompt is open.
+/// Up/Down only cycle through entries that start with whatever the user had
+/// typed before the first Up, and the in-progress edit is kept as a
+/// `working_copy` so recalling a line never mutates the saved entry.
+pub struct HistoryCursor<'a> {
+    history: &'a History,
+    filtered: Vec<usize>,
+    index: Option<usize>,
+    working_copy: Option<String>,
+}
+
+impl<'a> HistoryCursor<'a> {
+    pub fn new(history: &'a History) -> HistoryCursor<'a> {
+        HistoryCursor {
+            history,
+            filtered: Vec::new(),
+            index: None,
+            working_copy: None,
+        }
+    }
+
+    /// Forget the current navigation state. Called whenever the user types
+    /// a printable character, so the next Up starts a fresh prefix search.
+    pub fn reset(&mut self) {
+        self.filtered.clear();
+        self.index = None;
+        self.working_copy = None;
+    }
+
+    /// Recall the previous matching entry, entering navigation mode (and
+    /// capturing `current_buffer` as the prefix/working copy) on the first
+    /// call.
+    pub fn prev(&mut self, current_buffer: &str) -> Option<String> {
+        if self.index.is_none() {
+            self.working_copy = Some(current_buffer.to_string());
+            self.filtered = self
+                .history
+                .entries
+                .iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, entry)| entry.starts_with(current_buffer))
+                .map(|(i, _)| i)
+                .collect();
+        }
+
+        let next_index = match self.index {
+            None => 0,
+            Some(i) if i + 1 < self.filtered.len() => i + 1,
+            Some(i) => i,
+        };
+        self.index = Some(next_index);
+
+        self.filtered
+            .get(next_index)
+            .map(|&i| self.history.entries[i].clone())
+    }
+
+    /// Recall the next matching entry, restoring the original working copy
+    /// once the user steps past the newest match.
+    pub fn next(&mut self) -> Option<String> {
+        match self.index {
+            None => None,
+            Some(0) => {
+                self.index = None;
+                self.working_copy.take()
+            }
+            Some(i) => {
+                self.index = Some(i - 1);
+                self.filtered.get(i - 1).map(|&i| self.history.entries[i].clone())
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use syntect::highlighting::{Highlighter, HighlightIterator, HighlightState, Style, Theme, ThemeSet};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+use unicode_width::UnicodeWidthStr;
+
+use crate::coordinates::{Coordinates, Position, Size};
+
+/// Only preview files up to this size; anything larger (or anything that
+/// looks binary) falls back to a hex summary instead of being highlighted.
+const MAX_PREVIEW_BYTES: u64 = 16 * 1024 * 1024;
+
+/// How much of an oversized file to read for the hex fallback. There's no
+/// point reading the whole thing just to show a handful of rows.
+const HEX_PREVIEW_BYTES: u64 = 256 * 1024;
+
+/// Re-parse from the nearest of these checkpoints instead of from line 0, so
+/// scrolling a large file doesn't re-highlight the whole prefix every frame.
+const CHECKPOINT_INTERVAL: usize = 200;
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+fn theme() -> &'static Theme {
+    &THEME_SET.themes["base16-ocean.dark"]
+}
+
+enum Contents {
+    Text { lines: Vec<String>, syntax_name: String },
+    Binary { bytes: Vec<u8> },
+}
+
+/// Lazily-highlighted, pageable preview of a file. Only the line ranges
+/// actually scrolled into view are parsed/highlighted; a handful of parser
+/// checkpoints are kept so jumping back up a large file doesn't mean
+/// re-parsing from the top.
+pub struct Preview {
+    contents: Contents,
+    checkpoints: Vec<(usize, ParseState, HighlightState)>,
+}
+
+impl Preview {
+    pub fn load(path: &Path) -> Preview {
+        let is_oversized = std::fs::metadata(path)
+            .map(|meta| meta.len() > MAX_PREVIEW_BYTES)
+            .unwrap_or(true);
+
+        let bytes = if is_oversized {
+            read_prefix(path, HEX_PREVIEW_BYTES)
+        } else {
+            std::fs::read(path).unwrap_or_default()
+        };
+
+        if is_oversized || looks_binary(&bytes) {
+            return Preview {
+                contents: Contents::Binary { bytes },
+                checkpoints: Vec::new(),
+            };
+        }
+
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        let syntax_name = SYNTAX_SET
+            .find_syntax_for_file(path)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+            .name
+            .clone();
+
+        let syntax = SYNTAX_SET.find_syntax_by_name(&syntax_name).unwrap();
+        let highlighter = Highlighter::new(theme());
+        let checkpoints = vec![(
+            0,
+            ParseState::new(syntax),
+            HighlightState::new(&highlighter, ScopeStack::new()),
+        )];
+
+        Preview {
+            contents: Contents::Text { lines, syntax_name },
+            checkpoints,
+        }
+    }
+
+    /// ANSI-colored, width-truncated drawlist for the page of lines starting
+    /// at `start`, positioned to fit inside `coordinates`.
+    pub fn get_drawlist(&mut self, start: usize, coordinates: &Coordinates) -> String {
+        match &self.contents {
+            Contents::Binary { bytes } => hex_drawlist(bytes, start, coordinates),
+            Contents::Text { .. } => self.text_drawlist(start, coordinates),
+        }
+    }
+
+    fn text_drawlist(&mut self, start: usize, coordinates: &Coordinates) -> String {
+        let lines = match &self.contents {
+            Contents::Text { lines, .. } => lines,
+            Contents::Binary { .. } => unreachable!(),
+        };
+
+        let highlighter = Highlighter::new(theme());
+        let (checkpoint_line, mut parse_state, mut highlight_state) = self.nearest_checkpoint(start);
+
+        let Size((cols, rows)) = coordinates.size;
+        let Position((px, py)) = coordinates.position;
+        let mut drawlist = String::new();
+        let mut line_no = checkpoint_line;
+        let mut new_checkpoints = Vec::new();
+
+        while line_no < start + rows as usize && line_no < lines.len() {
+            if line_no % CHECKPOINT_INTERVAL == 0
+                && !self.checkpoints.iter().any(|(n, _, _)| *n == line_no)
+            {
+                new_checkpoints.push((line_no, parse_state.clone(), highlight_state.clone()));
+            }
+
+            let line = &lines[line_no];
+            let ops = parse_state.parse_line(line, &SYNTAX_SET).unwrap_or_default();
+            let ranges: Vec<(Style, &str)> =
+                HighlightIterator::new(&mut highlight_state, &ops, line, &highlighter).collect();
+
+            if line_no >= start {
+                drawlist += &format!("{}", termion::cursor::Goto(px, py + (line_no - start) as u16));
+                drawlist += &render_truncated(&ranges, cols as usize);
+            }
+
+            line_no += 1;
+        }
+
+        self.checkpoints.extend(new_checkpoints);
+
+        drawlist
+    }
+
+    /// Returns the state to resume parsing/highlighting *at* the returned
+    /// line number, i.e. the checkpoint was captured before that line was
+    /// processed.
+    fn nearest_checkpoint(&self, start: usize) -> (usize, ParseState, HighlightState) {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|(line, _, _)| *line <= start)
+            .cloned()
+            .unwrap_or_else(|| self.checkpoints[0].clone())
+    }
+}
+
+fn read_prefix(path: &Path, limit: u64) -> Vec<u8> {
+    use std::io::Read;
+
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut buf = Vec::new();
+    file.take(limit).read_to_end(&mut buf).ok();
+    buf
+}
+
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(4096).any(|&b| b == 0)
+}
+
+fn hex_drawlist(bytes: &[u8], start: usize, coordinates: &Coordinates) -> String {
+    let Size((_, rows)) = coordinates.size;
+    let Position((px, py)) = coordinates.position;
+    let bytes_per_row = 16;
+
+    let mut drawlist = String::new();
+    for row in 0..rows as usize {
+        let offset = (start + row) * bytes_per_row;
+        if offset >= bytes.len() {
+            break;
+        }
+        let chunk = &bytes[offset..(offset + bytes_per_row).min(bytes.len())];
+        let hex = chunk.iter().map(|b| format!("{:02x} ", b)).collect::<String>();
+
+        drawlist += &format!("{}", termion::cursor::Goto(px, py + row as u16));
+        drawlist += &format!("{:08x}  {}", offset, hex);
+    }
+    drawlist
+}
+
+fn render_truncated(ranges: &[(Style, &str)], max_width: usize) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+
+    for (style, text) in ranges {
+        for grapheme in unicode_segmentation(text) {
+            let grapheme_width = UnicodeWidthStr::width(grapheme);
+            if width + grapheme_width > max_width {
+                return out;
+            }
+            out += &format!(
+                "\x1b[38;2;{};{};{}m{}",
+                style.foreground.r, style.foreground.g, style.foreground.b, grapheme
+            );
+            width += grapheme_width;
+        }
+    }
+    out += "\x1b[0m";
+    out
+}
+
+fn unicode_segmentation(text: &str) -> impl Iterator<Item = &str> {
+    use unicode_segmentation::UnicodeSegmentation;
+    text.graphemes(true)
+}
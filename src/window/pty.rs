@@ -0,0 +1,211 @@
+use std::io::{Read, Write};
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+
+use crate::coordinates::{Coordinates, Position, Size};
+
+use super::Event;
+
+/// A child process running inside a pseudo-terminal, rendered into a
+/// sub-region of the `Window`. Output is fed into a `vt100::Parser` on a
+/// background thread; `get_drawlist` turns the parser's current screen into
+/// termion-positioned output that can be concatenated with the rest of the
+/// window's drawlist.
+pub struct PtyPane {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    parser: Arc<Mutex<vt100::Parser>>,
+    coordinates: Coordinates,
+    exit_status: Arc<Mutex<Option<ExitStatus>>>,
+    saved_coordinates: Option<Coordinates>,
+}
+
+impl PtyPane {
+    pub fn spawn(
+        cmd: &str,
+        args: &[String],
+        coordinates: Coordinates,
+        event_tx: async_channel::Sender<Event>,
+    ) -> std::io::Result<PtyPane> {
+        let Size((cols, rows)) = coordinates.size;
+
+        let pty_system = native_pty_system();
+        let pair = pty_system
+            .openpty(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(to_io_error)?;
+
+        let mut builder = CommandBuilder::new(cmd);
+        builder.args(args);
+        let mut child = pair.slave.spawn_command(builder).map_err(to_io_error)?;
+
+        let mut reader = pair.master.try_clone_reader().map_err(to_io_error)?;
+        let writer = pair.master.take_writer().map_err(to_io_error)?;
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0)));
+
+        {
+            let parser = parser.clone();
+            let event_tx = event_tx.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            parser.lock().unwrap().process(&buf[..n]);
+                            event_tx.try_send(Event::Redraw).ok();
+                        }
+                    }
+                }
+            });
+        }
+
+        let exit_status = Arc::new(Mutex::new(None));
+        {
+            let exit_status = exit_status.clone();
+            std::thread::spawn(move || {
+                if let Ok(status) = child.wait() {
+                    *exit_status.lock().unwrap() = Some(status);
+                    event_tx
+                        .try_send(Event::Status(format!("process exited: {}", status)))
+                        .ok();
+                }
+            });
+        }
+
+        Ok(PtyPane {
+            master: pair.master,
+            writer,
+            parser,
+            coordinates,
+            exit_status,
+            saved_coordinates: None,
+        })
+    }
+
+    /// Forward a key pressed while this pane is focused to the child
+    /// process.
+    pub fn send_key(&mut self, key: KeyEvent) {
+        self.writer.write_all(&encode_key(key)).ok();
+        self.writer.flush().ok();
+    }
+
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        *self.exit_status.lock().unwrap()
+    }
+
+    pub fn resize(&mut self, coordinates: &Coordinates) {
+        self.coordinates = coordinates.clone();
+        let Size((cols, rows)) = coordinates.size;
+        self.master
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .ok();
+        self.parser.lock().unwrap().set_size(rows, cols);
+    }
+
+    /// Temporarily grow the pane to cover the whole alternate screen.
+    /// Calling this again while already fullscreen restores the previous
+    /// sub-region.
+    pub fn toggle_fullscreen(&mut self, full_screen: Coordinates) {
+        match self.saved_coordinates.take() {
+            Some(previous) => self.resize(&previous),
+            None => {
+                self.saved_coordinates = Some(self.coordinates.clone());
+                self.resize(&full_screen);
+            }
+        }
+    }
+
+    pub fn get_drawlist(&self) -> String {
+        let parser = self.parser.lock().unwrap();
+        let screen = parser.screen();
+        let Position((px, py)) = self.coordinates.position;
+        let Size((cols, rows)) = self.coordinates.size;
+
+        let mut drawlist = String::new();
+        for row in 0..rows {
+            drawlist += &format!("{}", termion::cursor::Goto(px, py + row));
+            for col in 0..cols {
+                match screen.cell(row, col) {
+                    Some(cell) => {
+                        drawlist += &cell_sgr(&cell);
+                        let contents = cell.contents();
+                        drawlist += if contents.is_empty() { " " } else { &contents };
+                    }
+                    None => {
+                        drawlist += "\x1b[0m ";
+                    }
+                }
+            }
+        }
+        drawlist += "\x1b[0m";
+        drawlist
+    }
+}
+
+/// Translates a `vt100::Cell`'s colors and attributes into an ANSI SGR
+/// escape, so the embedded pane keeps the coloring of whatever's running in
+/// it (syntax highlighting, `ls --color`, a pager) instead of going flat.
+fn cell_sgr(cell: &vt100::Cell) -> String {
+    let mut codes = Vec::new();
+
+    match cell.fgcolor() {
+        vt100::Color::Default => {}
+        vt100::Color::Idx(i) => codes.push(format!("38;5;{}", i)),
+        vt100::Color::Rgb(r, g, b) => codes.push(format!("38;2;{};{};{}", r, g, b)),
+    }
+    match cell.bgcolor() {
+        vt100::Color::Default => {}
+        vt100::Color::Idx(i) => codes.push(format!("48;5;{}", i)),
+        vt100::Color::Rgb(r, g, b) => codes.push(format!("48;2;{};{};{}", r, g, b)),
+    }
+    if cell.bold() {
+        codes.push("1".to_string());
+    }
+    if cell.underline() {
+        codes.push("4".to_string());
+    }
+    if cell.inverse() {
+        codes.push("7".to_string());
+    }
+
+    if codes.is_empty() {
+        "\x1b[0m".to_string()
+    } else {
+        format!("\x1b[0;{}m", codes.join(";"))
+    }
+}
+
+fn encode_key(key: KeyEvent) -> Vec<u8> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            vec![(c as u8) & 0x1f]
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
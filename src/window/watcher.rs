@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher};
+
+use super::Event;
+
+/// Debounce window within which a burst of create/remove/rename events for
+/// the same paths is coalesced into a single `Event::Fs` per path.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Upper bound on how long a single batch can be held back. A path that's
+/// continuously busy (a log being appended to, a build writing file after
+/// file) would otherwise never go quiet for `DEBOUNCE` and would starve the
+/// widget of any updates at all.
+const MAX_BATCH_LATENCY: Duration = Duration::from_millis(500);
+
+/// Watches filesystem paths registered via `Window::watch` and forwards
+/// debounced change notifications onto the window's event bus, so an open
+/// widget can `refresh()` without polling.
+pub struct FsWatcher {
+    watcher: RecommendedWatcher,
+}
+
+impl FsWatcher {
+    pub fn new(event_tx: async_channel::Sender<Event>) -> notify::Result<FsWatcher> {
+        let (raw_tx, raw_rx) = std::sync::mpsc::channel();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                for path in event.paths {
+                    raw_tx.send(path).ok();
+                }
+            }
+        })?;
+
+        tokio::task::spawn_blocking(move || debounce_loop(raw_rx, event_tx));
+
+        Ok(FsWatcher { watcher })
+    }
+
+    pub fn watch(&mut self, path: &Path) {
+        self.watcher.watch(path, RecursiveMode::NonRecursive).ok();
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.watcher.unwatch(path).ok();
+    }
+}
+
+/// Runs on a blocking task: collapses bursts arriving within `DEBOUNCE` of
+/// each other (or `MAX_BATCH_LATENCY` of the first event in the batch,
+/// whichever comes first) into one `Event::Fs` per distinct path.
+fn debounce_loop(raw_rx: std::sync::mpsc::Receiver<PathBuf>, event_tx: async_channel::Sender<Event>) {
+    let mut pending: HashSet<PathBuf> = HashSet::new();
+
+    while let Ok(path) = raw_rx.recv() {
+        pending.insert(path);
+        let batch_start = Instant::now();
+
+        loop {
+            let remaining = MAX_BATCH_LATENCY.saturating_sub(batch_start.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            match raw_rx.recv_timeout(DEBOUNCE.min(remaining)) {
+                Ok(path) => {
+                    pending.insert(path);
+                }
+                Err(_) => break,
+            }
+        }
+
+        for path in pending.drain() {
+            event_tx.try_send(Event::Fs(path)).ok();
+        }
+    }
+}